@@ -0,0 +1,42 @@
+// A small case-insensitive header map. Keys are normalized to lowercase on
+// insert (the same way actix folds `Keep-Alive`/`keep-alive`/`KEEP-ALIVE`
+// together), so a lookup just lowercases the query and compares it against
+// the stored key directly.
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+#[derive(Default)]
+pub struct HeaderMap<'buf> {
+    headers: Vec<(String, &'buf str)>,
+}
+
+impl<'buf> HeaderMap<'buf> {
+    pub fn new() -> Self {
+        Self { headers: Vec::new() }
+    }
+
+    pub fn insert(&mut self, name: &str, value: &'buf str) {
+        self.headers.push((name.to_ascii_lowercase(), value));
+    }
+
+    // Case-insensitive lookup, e.g. `get("content-type")` matches a
+    // `Content-Type:` line.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &'buf str)> {
+        self.headers.iter().map(|(name, value)| (name.as_str(), *value))
+    }
+}
+
+impl<'buf> Debug for HeaderMap<'buf> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_map()
+            .entries(self.headers.iter().map(|(name, value)| (name.as_str(), *value)))
+            .finish()
+    }
+}
@@ -0,0 +1,13 @@
+pub mod method;
+mod query_string;
+pub mod request;
+pub mod response;
+pub mod status_code;
+
+mod header_map;
+
+pub use method::Method;
+pub use query_string::{QueryString, QueryStringValue};
+pub use request::{ParseError, Request, Version};
+pub use response::Response;
+pub use status_code::StatusCode;
@@ -1,4 +1,5 @@
 // Super means: go 1 level up to parent
+use super::header_map::HeaderMap;
 use super::method::{Method, MethodError};
 use super::{QueryString, QueryStringValue};
 use std::convert::TryFrom;
@@ -15,6 +16,9 @@ pub struct Request<'buf> {
     // -    TakesEither `None` or `Some(String)`
     query_string: Option<QueryString<'buf>>,
     method: Method,
+    headers: HeaderMap<'buf>,
+    body: &'buf [u8],
+    version: Version,
 }
 
 impl<'buf> Request<'buf> {
@@ -30,6 +34,25 @@ impl<'buf> Request<'buf> {
     pub fn query_string(&self) -> Option<&QueryString> {
         self.query_string.as_ref()
     }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn body(&self) -> &[u8] {
+        self.body
+    }
+
+    // The server reads the message body separately, once it knows from the
+    // headers whether to expect `Content-Length` bytes or a chunked stream,
+    // and attaches the fully-decoded body here.
+    pub(crate) fn set_body(&mut self, body: &'buf [u8]) {
+        self.body = body;
+    }
 }
 
 // using 'buf lifetime to guarantee compiler memory safety &
@@ -40,8 +63,13 @@ impl<'buf> TryFrom<&'buf [u8]> for Request<'buf> {
 
     // e.g., GET /search?name=abc&sort=1 HTTP/1.1\r\n...HEADERS...
     fn try_from(buf: &'buf [u8]) -> Result<Request<'buf>, Self::Error> {
+        // Only the request-line + header block has to be text; the body
+        // (images, protobuf, any other `Content-Type`) is opaque bytes and
+        // must not be rejected just because it isn't valid UTF-8.
+        let (header_bytes, body) = split_headers_and_body(buf);
+
         // Makes sure bytes in buf are UTF
-        let request = str::from_utf8(buf)?;
+        let request = str::from_utf8(header_bytes)?;
 
         // transforms option into result by looking at option:
         // -    if option is Some, convert to Ok variant of result
@@ -53,12 +81,14 @@ impl<'buf> TryFrom<&'buf [u8]> for Request<'buf> {
         // Second call == `/search?name=abc&sort=1`
         let (mut path, request) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
 
-        // Third call == `HTTP/1.1`
-        let (protocol, _) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
+        // Third call == `HTTP/1.1` (or `HTTP/1.0`)
+        let (protocol, remainder) = get_next_word(request).ok_or(ParseError::InvalidRequest)?;
 
-        if protocol != "HTTP/1.1" {
-            return Err(ParseError::InvalidProtocol);
-        }
+        let version = match protocol {
+            "HTTP/1.1" => Version::Http11,
+            "HTTP/1.0" => Version::Http10,
+            _ => return Err(ParseError::InvalidProtocol),
+        };
 
         // convert type into another type (e.g, String to Enum)
         let method: Method = method.parse()?;
@@ -71,14 +101,89 @@ impl<'buf> TryFrom<&'buf [u8]> for Request<'buf> {
             path = &path[..i];
         }
 
+        let headers = parse_headers(remainder)?;
+
         Ok(Self {
             path,
             query_string,
             method,
+            headers,
+            // Whatever the same read already pulled in past the headers is
+            // our best guess at the body; `Server::run` replaces this with
+            // the fully read/decoded body once it knows how much to expect.
+            body,
+            version,
         })
     }
 }
 
+// Splits the raw buffer at the `\r\n\r\n` boundary that ends the header
+// block, e.g:
+//      GET / HTTP/1.1\r\n
+//      Host: localhost\r\n
+//      \r\n
+//      {opaque body bytes}
+// so only the first half ever needs to go through UTF-8 validation. If the
+// boundary hasn't arrived yet (a partial read only got part of the
+// headers), there's no body yet either.
+fn split_headers_and_body(buf: &[u8]) -> (&[u8], &[u8]) {
+    match buf.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(i) => buf.split_at(i + 4),
+        None => (buf, &buf[buf.len()..]),
+    }
+}
+
+// The two protocol versions this server understands. HTTP/1.0 and HTTP/1.1
+// differ in their keep-alive defaults: a 1.0 connection closes after one
+// request unless told `Connection: keep-alive`, while a 1.1 connection stays
+// open unless told `Connection: close`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let version = match self {
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+        };
+
+        write!(f, "{}", version)
+    }
+}
+
+// Everything after the request line, up to (but not including) the blank
+// `\r\n` that terminates the header block, e.g:
+//      Host: localhost\r\n
+//      Content-Type: text/plain\r\n
+//      \r\n
+fn parse_headers(remainder: &str) -> Result<HeaderMap, ParseError> {
+    let mut headers = HeaderMap::new();
+    // `get_next_word` on the request line leaves us right after the `\r` of
+    // `HTTP/1.1\r`, so the first character left is the `\n` of that line's
+    // terminator.
+    let mut rest = remainder.strip_prefix('\n').unwrap_or(remainder);
+
+    while let Some(i) = rest.find("\r\n") {
+        let line = &rest[..i];
+        rest = &rest[i + 2..];
+
+        if line.is_empty() {
+            // The `\r\n\r\n` boundary: no more headers follow.
+            return Ok(headers);
+        }
+
+        let colon = line.find(':').ok_or(ParseError::InvalidHeader)?;
+        let name = line[..colon].trim();
+        let value = line[colon + 1..].trim();
+        headers.insert(name, value);
+    }
+
+    Ok(headers)
+}
+
 // e.g, for:  GET /search?name=abc&sort=1 HTTP/1.1
 // 1. GET
 // 2. pass in: /search?name=abc&sort=1 HTTP/1.1
@@ -103,6 +208,9 @@ pub enum ParseError {
     InvalidEncoding, // Not UTF encoded
     InvalidProtocol, // Requests that have invalid http version
     InvalidMethod,   // Not one of the methods in enum
+    InvalidHeader,   // Header line missing a `:` separator, or an unparseable header value
+    IncompleteBody,  // Connection EOF'd or failed before the advertised body arrived, or chunked framing was malformed
+    PayloadTooLarge, // Content-Length announced a body bigger than we'll read
 }
 
 impl ParseError {
@@ -112,6 +220,9 @@ impl ParseError {
             Self::InvalidEncoding => "Invalid Encoding",
             Self::InvalidProtocol => "Invalid Protocol",
             Self::InvalidMethod => "Invalid Method",
+            Self::InvalidHeader => "Invalid Header",
+            Self::IncompleteBody => "Incomplete Body",
+            Self::PayloadTooLarge => "Payload Too Large",
         }
     }
 }
@@ -141,3 +252,47 @@ impl Debug for ParseError {
 }
 
 impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_headers_case_insensitively_and_stops_at_the_blank_line() {
+        let raw = b"GET /search?name=abc HTTP/1.1\r\nHost: localhost\r\nUser-Agent: curl\r\n\r\nbody follows";
+        let request = Request::try_from(&raw[..]).unwrap();
+
+        assert_eq!(request.headers().get("host"), Some("localhost"));
+        assert_eq!(request.headers().get("HOST"), Some("localhost"));
+        assert_eq!(request.headers().get("user-agent"), Some("curl"));
+        assert_eq!(request.headers().get("missing"), None);
+    }
+
+    #[test]
+    fn does_not_reject_a_body_that_isnt_valid_utf8() {
+        let mut raw = b"POST /upload HTTP/1.1\r\nContent-Length: 4\r\n\r\n".to_vec();
+        raw.extend_from_slice(&[0xFF, 0xFE, 0xFD, 0xFC]); // not valid UTF-8
+
+        let request = Request::try_from(&raw[..]).unwrap();
+
+        assert_eq!(request.body(), &[0xFF, 0xFE, 0xFD, 0xFC]);
+    }
+
+    #[test]
+    fn rejects_a_header_line_missing_a_colon() {
+        let raw = b"GET / HTTP/1.1\r\nBroken Header\r\n\r\n";
+
+        let result = Request::try_from(&raw[..]);
+
+        assert!(matches!(result, Err(ParseError::InvalidHeader)));
+    }
+
+    #[test]
+    fn accepts_both_http_1_0_and_http_1_1() {
+        let http10 = Request::try_from(&b"GET / HTTP/1.0\r\n\r\n"[..]).unwrap();
+        let http11 = Request::try_from(&b"GET / HTTP/1.1\r\n\r\n"[..]).unwrap();
+
+        assert_eq!(http10.version(), Version::Http10);
+        assert_eq!(http11.version(), Version::Http11);
+    }
+}
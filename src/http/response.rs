@@ -0,0 +1,59 @@
+use super::{StatusCode, Version};
+use std::io::{Result as IoResult, Write};
+
+#[derive(Debug)]
+pub struct Response {
+    status_code: StatusCode,
+    body: Option<String>,
+    headers: Vec<(String, String)>,
+    // Defaults to 1.1; `Server` overrides this with the request's own
+    // version so an HTTP/1.0 client doesn't get answered with a 1.1 status
+    // line.
+    version: Version,
+}
+
+impl Response {
+    pub fn new(status_code: StatusCode, body: Option<String>) -> Self {
+        Response {
+            status_code,
+            body,
+            headers: Vec::new(),
+            version: Version::Http11,
+        }
+    }
+
+    // Builder-style so callers can chain additional headers onto a response
+    // before sending it, e.g. `Response::new(...).with_header("Connection", "close")`.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    // Lets the server echo back the protocol version the request came in
+    // on, e.g. `response.with_version(request.version())`.
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn send(&self, stream: &mut impl Write) -> IoResult<()> {
+        let body = match &self.body {
+            Some(b) => b.as_str(),
+            None => "",
+        };
+
+        write!(
+            stream,
+            "{} {} {}\r\n",
+            self.version,
+            self.status_code,
+            self.status_code.reason_phrase()
+        )?;
+
+        for (name, value) in &self.headers {
+            write!(stream, "{}: {}\r\n", name, value)?;
+        }
+
+        write!(stream, "\r\n{}", body)
+    }
+}
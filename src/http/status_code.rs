@@ -0,0 +1,36 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+#[derive(Copy, Clone, Debug)]
+pub enum StatusCode {
+    Continue,
+    Ok,
+    BadRequest,
+    NotFound,
+    ExpectationFailed,
+}
+
+impl StatusCode {
+    pub fn reason_phrase(&self) -> &str {
+        match self {
+            Self::Continue => "Continue",
+            Self::Ok => "OK",
+            Self::BadRequest => "Bad Request",
+            Self::NotFound => "Not Found",
+            Self::ExpectationFailed => "Expectation Failed",
+        }
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let code = match self {
+            Self::Continue => 100,
+            Self::Ok => 200,
+            Self::BadRequest => 400,
+            Self::NotFound => 404,
+            Self::ExpectationFailed => 417,
+        };
+
+        write!(f, "{}", code)
+    }
+}
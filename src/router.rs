@@ -0,0 +1,205 @@
+use crate::http::{Method, Request, Response, StatusCode};
+use crate::server::Handler;
+use std::collections::HashMap;
+
+// Path parameters captured out of `:name` segments (and the trailing
+// `*rest` wildcard, if the route has one), keyed by the name the route was
+// registered with.
+pub type Params<'buf> = HashMap<&'buf str, &'buf str>;
+
+type RouteHandler = Box<dyn Fn(&Request, &Params) -> Response>;
+type NotFoundHandler = Box<dyn Fn(&Request) -> Response>;
+
+// One segment's worth of the registered routes, e.g. the `users` in
+// `/users/:id`. Each node can have any mix of a literal child per segment
+// text, a single `:name` child, and a trailing `*rest` wildcard.
+#[derive(Default)]
+struct Node {
+    handlers: HashMap<Method, RouteHandler>,
+    static_children: HashMap<String, Node>,
+    param_child: Option<(String, Box<Node>)>,
+    wildcard: Option<(String, HashMap<Method, RouteHandler>)>,
+}
+
+// Maps registered path patterns like `/users/:id/posts/:post_id` to
+// per-`Method` handlers, inspired by actix's use of route-recognizer.
+//
+//      Router::new()
+//          .route(Method::GET, "/users/:id", get_user)
+//          .route(Method::GET, "/files/*path", serve_file)
+//          .not_found(render_404)
+#[derive(Default)]
+pub struct Router {
+    root: Node,
+    not_found: Option<NotFoundHandler>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(
+        mut self,
+        method: Method,
+        pattern: &str,
+        handler: impl Fn(&Request, &Params) -> Response + 'static,
+    ) -> Self {
+        let mut node = &mut self.root;
+
+        let mut segments = pattern.split('/').filter(|segment| !segment.is_empty()).peekable();
+        while let Some(segment) = segments.next() {
+            if let Some(name) = segment.strip_prefix('*') {
+                assert!(
+                    segments.peek().is_none(),
+                    "`*{}` must be the last segment of the route pattern `{}`",
+                    name,
+                    pattern
+                );
+
+                let (_, handlers) = node
+                    .wildcard
+                    .get_or_insert_with(|| (name.to_string(), HashMap::new()));
+                handlers.insert(method, Box::new(handler));
+                return self;
+            }
+
+            node = if let Some(name) = segment.strip_prefix(':') {
+                &mut node
+                    .param_child
+                    .get_or_insert_with(|| (name.to_string(), Box::new(Node::default())))
+                    .1
+            } else {
+                node.static_children.entry(segment.to_string()).or_insert_with(Node::default)
+            };
+        }
+
+        node.handlers.insert(method, Box::new(handler));
+        self
+    }
+
+    // Handler to fall back on when no registered route matches the request.
+    // Defaults to a bare `404 Not Found`.
+    pub fn not_found(mut self, handler: impl Fn(&Request) -> Response + 'static) -> Self {
+        self.not_found = Some(Box::new(handler));
+        self
+    }
+
+    fn find<'r>(&self, path: &'r str, method: &Method) -> Option<(&RouteHandler, Params<'r>)> {
+        let rest = path.trim_start_matches('/');
+        let mut params = Params::new();
+
+        Self::find_in(&self.root, rest, method, &mut params).map(|handler| (handler, params))
+    }
+
+    // A static child that matches the next segment can still dead-end
+    // further down the tree (e.g. `/users/admin` has no handler even though
+    // `/users/admin/settings` does), so we can't just commit to the first
+    // branch that matches. Try static first since it's the most specific,
+    // then backtrack into `:param`, then `*wildcard`, same as
+    // route-recognizer does.
+    fn find_in<'r>(
+        node: &Node,
+        rest: &'r str,
+        method: &Method,
+        params: &mut Params<'r>,
+    ) -> Option<&RouteHandler> {
+        if rest.is_empty() {
+            return node.handlers.get(method);
+        }
+
+        let (segment, after) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => (rest, ""),
+        };
+
+        if let Some(child) = node.static_children.get(segment) {
+            if let Some(handler) = Self::find_in(child, after, method, params) {
+                return Some(handler);
+            }
+        }
+
+        if let Some((name, child)) = &node.param_child {
+            let mut candidate = params.clone();
+            candidate.insert(name.as_str(), segment);
+
+            if let Some(handler) = Self::find_in(child, after, method, &mut candidate) {
+                *params = candidate;
+                return Some(handler);
+            }
+        }
+
+        if let Some((name, handlers)) = &node.wildcard {
+            if let Some(handler) = handlers.get(method) {
+                params.insert(name.as_str(), rest);
+                return Some(handler);
+            }
+        }
+
+        None
+    }
+}
+
+impl Handler for Router {
+    fn handle_request(&mut self, request: &Request) -> Response {
+        match self.find(request.path(), request.method()) {
+            Some((handler, params)) => handler(request, &params),
+            None => match &self.not_found {
+                Some(handler) => handler(request),
+                None => Response::new(StatusCode::NotFound, None),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop_handler(_: &Request, _: &Params) -> Response {
+        Response::new(StatusCode::Ok, None)
+    }
+
+    #[test]
+    fn backtracks_from_a_dead_end_static_branch_into_a_param_branch() {
+        let router = Router::new()
+            .route(Method::GET, "/users/:id", noop_handler)
+            .route(Method::GET, "/users/admin/settings", noop_handler);
+
+        // `/users/admin` takes the `admin` static branch first (it exists,
+        // because of `/users/admin/settings`), dead-ends with no GET
+        // handler there, and must backtrack into `/users/:id`.
+        let (_, params) = router
+            .find("/users/admin", &Method::GET)
+            .expect("should fall back to the `:id` route");
+
+        assert_eq!(params.get("id"), Some(&"admin"));
+    }
+
+    #[test]
+    fn wildcard_only_matches_when_no_more_specific_branch_does() {
+        let router = Router::new()
+            .route(Method::GET, "/files/report.pdf", noop_handler)
+            .route(Method::GET, "/files/*path", noop_handler);
+
+        let (_, params) = router
+            .find("/files/images/logo.png", &Method::GET)
+            .expect("should match the wildcard route");
+
+        assert_eq!(params.get("path"), Some(&"images/logo.png"));
+
+        // The static `report.pdf` route wins over the wildcard, so nothing
+        // gets captured into `path`.
+        let (_, params) = router
+            .find("/files/report.pdf", &Method::GET)
+            .expect("should match the static route");
+
+        assert_eq!(params.get("path"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "must be the last segment")]
+    fn rejects_a_wildcard_that_isnt_the_last_segment() {
+        Router::new().route(Method::GET, "/files/*path/extra", noop_handler);
+    }
+}
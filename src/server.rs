@@ -1,8 +1,20 @@
-use crate::http::{ParseError, Request, Response, StatusCode};
+use crate::http::{ParseError, Request, Response, StatusCode, Version};
+use crate::thread_pool::ThreadPool;
 use std::convert::TryFrom;
 use std::convert::TryInto;
-use std::io::{Read, Write};
-use std::net::TcpListener;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str;
+use std::time::Duration;
+
+// How long an idle keep-alive connection is allowed to sit between requests
+// before we give up on it and move on to the next one.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Bodies larger than this are rejected: with `417 Expectation Failed` before
+// the client sends them when it asked `Expect: 100-continue`, and otherwise
+// as `ParseError::PayloadTooLarge`, answered by `handle_bad_request`.
+const MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
 
 pub trait Handler {
     fn handle_request(&mut self, request: &Request) -> Response;
@@ -37,32 +49,365 @@ impl Server {
         // check for new connections
         loop {
             match listener.accept() {
-                Ok((mut stream, _)) => {
-                    let mut buffer = [0; 1024];
-
-                    match stream.read(&mut buffer) {
-                        Ok(_) => {
-                            // `_lossy` never fails, but replaces with `?` symbol
-                            println!("Received a request: {}", String::from_utf8_lossy(&buffer));
-
-                            // Match on result from request
-                            let response = match Request::try_from(&buffer[..]) {
-                                Ok(request) => handler.handle_request(&request),
-                                Err(e) => handler.handle_bad_request(&e),
-                            };
-
-                            // Send response to TcpStream
-                            if let Err(e) = response.send(&mut stream) {
-                                println!("Failed to send response: {}", e)
-                            }
-                        }
-                        Err(e) => println!("Failed to read from connection: {}", e),
-                    }
+                Ok((stream, _)) => Self::handle_connection(stream, &mut handler),
+                Err(e) => println!("Failed to establish a connection: {}", e),
+            }
+        }
+    }
 
-                    println!("OK")
+    // Same as `run`, but each accepted connection is handed off to a fixed
+    // pool of `workers` threads instead of being handled inline, so one
+    // slow client can't stall everyone behind it. Each accepted connection
+    // gets its own clone of `handler`, which is why `Handler` must be `Clone`
+    // here.
+    pub fn run_threaded(self, handler: impl Handler + Send + Clone + 'static, workers: usize) {
+        println!("Listening on {} ({} workers)", self.addr, workers);
+
+        let listener = TcpListener::bind(&self.addr).unwrap();
+        let pool = ThreadPool::new(workers);
+
+        for connection in listener.incoming() {
+            match connection {
+                Ok(stream) => {
+                    let mut handler = handler.clone();
+                    pool.execute(move || Self::handle_connection(stream, &mut handler));
                 }
                 Err(e) => println!("Failed to establish a connection: {}", e),
             }
         }
     }
+
+    // Keep reading and answering requests off the same `TcpStream` until the
+    // client asks us to close (`Connection: close`), goes quiet for longer
+    // than `KEEP_ALIVE_TIMEOUT`, or disconnects outright.
+    fn handle_connection(mut stream: TcpStream, handler: &mut impl Handler) {
+        if let Err(e) = stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT)) {
+            println!("Failed to set read timeout: {}", e);
+        }
+
+        // Bytes a previous iteration already pulled off the socket but
+        // didn't belong to that request's body -- the start of the next
+        // pipelined request, carried forward instead of being dropped.
+        let mut pending: Vec<u8> = Vec::new();
+
+        loop {
+            let mut buffer = [0; 1024];
+
+            let bytes_read = if !pending.is_empty() {
+                let n = pending.len().min(buffer.len());
+                buffer[..n].copy_from_slice(&pending[..n]);
+                pending.drain(..n);
+                n
+            } else {
+                match stream.read(&mut buffer) {
+                    Ok(0) => break, // Client closed the connection.
+                    Ok(n) => n,
+                    Err(e)
+                        if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut =>
+                    {
+                        break; // Idle keep-alive connection timed out.
+                    }
+                    Err(e) => {
+                        println!("Failed to read from connection: {}", e);
+                        break;
+                    }
+                }
+            };
+
+            // `_lossy` never fails, but replaces with `?` symbol
+            println!(
+                "Received a request: {}",
+                String::from_utf8_lossy(&buffer[..bytes_read])
+            );
+
+            // Match on result from request
+            let mut parsed_request = Request::try_from(&buffer[..bytes_read]);
+
+            if let Ok(request) = &parsed_request {
+                if Self::handle_expect(&mut stream, request) {
+                    break; // We rejected the request; don't try to read its body.
+                }
+            }
+
+            let mut body = Vec::new();
+
+            let body_result = match &mut parsed_request {
+                Ok(request) => Self::read_body(&mut stream, request, &mut body),
+                Err(_) => Ok(Vec::new()),
+            };
+            match body_result {
+                Ok(leftover) => pending.extend_from_slice(&leftover),
+                Err(e) => parsed_request = Err(e),
+            }
+
+            let keep_alive = parsed_request
+                .as_ref()
+                .map(Self::should_keep_alive)
+                .unwrap_or(false);
+            let version = parsed_request
+                .as_ref()
+                .map(|request| request.version())
+                .unwrap_or(Version::Http11);
+
+            let response = match parsed_request {
+                Ok(request) => handler.handle_request(&request),
+                Err(e) => handler.handle_bad_request(&e),
+            };
+
+            let response = response
+                .with_version(version)
+                .with_header("Connection", if keep_alive { "keep-alive" } else { "close" });
+
+            if let Err(e) = response.send(&mut stream) {
+                println!("Failed to send response: {}", e)
+            }
+
+            println!("OK");
+
+            if !keep_alive {
+                break;
+            }
+        }
+    }
+
+    // HTTP/1.1 defaults to keep-alive unless the client explicitly asks us
+    // to close the connection; HTTP/1.0 is the opposite, defaulting to
+    // close unless the client opts in.
+    fn should_keep_alive(request: &Request) -> bool {
+        match request.headers().get("connection") {
+            Some(value) if value.eq_ignore_ascii_case("close") => false,
+            Some(value) if value.eq_ignore_ascii_case("keep-alive") => true,
+            _ => request.version() == Version::Http11,
+        }
+    }
+
+    // Borrows actix-web's handling of `Expect: 100-continue`: tell the
+    // client to go ahead and send the body, unless it's already announced
+    // as bigger than we're willing to read, in which case we reject it
+    // with `417 Expectation Failed` before it wastes the bandwidth.
+    // Returns `true` if the request was rejected and the connection should
+    // move on without reading a body.
+    fn handle_expect(stream: &mut TcpStream, request: &Request) -> bool {
+        let expects_continue = request
+            .headers()
+            .get("expect")
+            .map(|value| value.eq_ignore_ascii_case("100-continue"))
+            .unwrap_or(false);
+
+        if !expects_continue {
+            return false;
+        }
+
+        let oversized = request
+            .headers()
+            .get("content-length")
+            .and_then(|value| value.parse::<usize>().ok())
+            .map(|length| length > MAX_BODY_SIZE)
+            .unwrap_or(false);
+
+        if oversized {
+            let response =
+                Response::new(StatusCode::ExpectationFailed, None).with_version(request.version());
+            if let Err(e) = response.send(stream) {
+                println!("Failed to send response: {}", e)
+            }
+            return true;
+        }
+
+        if let Err(e) = stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n") {
+            println!("Failed to send 100 Continue response: {}", e)
+        }
+
+        false
+    }
+
+    // `Request::try_from` only sees whatever bytes a single `read` happened
+    // to pull in alongside the headers. If the body is bigger than that (or
+    // chunked), keep reading off the same stream until we have it all, then
+    // attach the finished body to `request`. `storage` is owned by the
+    // caller so the bytes outlive this call.
+    //
+    // That same `read` may also have pulled in the start of the *next*
+    // pipelined request past the end of this one's body; whatever's left
+    // over is returned so the caller can feed it back in as the start of
+    // the next iteration instead of dropping it on the floor.
+    fn read_body<'buf>(
+        stream: &mut impl Read,
+        request: &mut Request<'buf>,
+        storage: &'buf mut Vec<u8>,
+    ) -> Result<Vec<u8>, ParseError> {
+        if let Some(length) = request.headers().get("content-length") {
+            // A non-numeric length is a malformed header, not a truncated
+            // body -- those are different problems with different causes.
+            let length: usize = length.parse().map_err(|_| ParseError::InvalidHeader)?;
+            if length > MAX_BODY_SIZE {
+                return Err(ParseError::PayloadTooLarge);
+            }
+
+            storage.extend_from_slice(request.body());
+            while storage.len() < length {
+                let mut chunk = [0; 1024];
+                let n = stream.read(&mut chunk).map_err(|_| ParseError::IncompleteBody)?;
+                if n == 0 {
+                    return Err(ParseError::IncompleteBody);
+                }
+                storage.extend_from_slice(&chunk[..n]);
+            }
+            let leftover = storage.split_off(length);
+
+            request.set_body(storage);
+            Ok(leftover)
+        } else if request
+            .headers()
+            .get("transfer-encoding")
+            .map(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+            .unwrap_or(false)
+        {
+            storage.extend_from_slice(request.body());
+            let leftover = Self::decode_chunked(stream, storage)?;
+
+            request.set_body(storage);
+            Ok(leftover)
+        } else {
+            // No declared body, so whatever the initial read already
+            // pulled in past the headers isn't ours -- it's the start of
+            // the next pipelined request.
+            let leftover = request.body().to_vec();
+            request.set_body(&[]);
+            Ok(leftover)
+        }
+    }
+
+    // Decodes `chunked` transfer-encoding in place: each chunk is a hex
+    // size, `\r\n`, that many body bytes, then a trailing `\r\n`, until a
+    // zero-size chunk marks the end. Returns whatever arrived after the
+    // terminating blank line, which belongs to the next pipelined request
+    // rather than this body.
+    fn decode_chunked(stream: &mut impl Read, storage: &mut Vec<u8>) -> Result<Vec<u8>, ParseError> {
+        let mut decoded = Vec::new();
+        let mut cursor = 0;
+
+        loop {
+            while let Some(offset) = find_crlf(&storage[cursor..]) {
+                let line_end = cursor + offset;
+                let size_line =
+                    str::from_utf8(&storage[cursor..line_end]).map_err(|_| ParseError::IncompleteBody)?;
+                let size = usize::from_str_radix(size_line.trim(), 16)
+                    .map_err(|_| ParseError::IncompleteBody)?;
+                let chunk_start = line_end + 2;
+
+                if size == 0 {
+                    // `chunk_start` is right after the terminal `0\r\n`; the
+                    // empty trailer section's closing `\r\n` may or may not
+                    // have arrived in this same read yet.
+                    let after_terminator = if storage[chunk_start..].starts_with(b"\r\n") {
+                        chunk_start + 2
+                    } else {
+                        chunk_start
+                    };
+                    let leftover = storage[after_terminator..].to_vec();
+                    *storage = decoded;
+                    return Ok(leftover);
+                }
+
+                let chunk_end = chunk_start + size;
+                if storage.len() < chunk_end + 2 {
+                    break; // This chunk isn't fully buffered yet; read more.
+                }
+
+                decoded.extend_from_slice(&storage[chunk_start..chunk_end]);
+                cursor = chunk_end + 2;
+            }
+
+            let mut chunk = [0; 1024];
+            let n = stream.read(&mut chunk).map_err(|_| ParseError::IncompleteBody)?;
+            if n == 0 {
+                return Err(ParseError::IncompleteBody);
+            }
+            storage.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|pair| pair == b"\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn decode_chunked_joins_chunks_and_stops_at_the_zero_size_terminator() {
+        let mut storage = b"4\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n".to_vec();
+        let mut rest = Cursor::new(Vec::new());
+
+        Server::decode_chunked(&mut rest, &mut storage).unwrap();
+
+        assert_eq!(storage, b"Wikipedia");
+    }
+
+    #[test]
+    fn decode_chunked_reads_more_when_a_chunk_is_split_across_reads() {
+        // Only half of the `Wiki` chunk has arrived so far.
+        let mut storage = b"4\r\nWi".to_vec();
+        let mut rest = Cursor::new(b"ki\r\n0\r\n\r\n".to_vec());
+
+        Server::decode_chunked(&mut rest, &mut storage).unwrap();
+
+        assert_eq!(storage, b"Wiki");
+    }
+
+    #[test]
+    fn decode_chunked_errors_on_eof_before_the_terminator() {
+        let mut storage = b"4\r\nWiki\r\n".to_vec();
+        let mut rest = Cursor::new(Vec::new());
+
+        let result = Server::decode_chunked(&mut rest, &mut storage);
+
+        assert!(matches!(result, Err(ParseError::IncompleteBody)));
+    }
+
+    #[test]
+    fn read_body_rejects_a_non_numeric_content_length_as_a_bad_header() {
+        let raw = b"GET / HTTP/1.1\r\nContent-Length: not-a-number\r\n\r\n";
+        let mut request = Request::try_from(&raw[..]).unwrap();
+        let mut storage = Vec::new();
+        let mut stream = Cursor::new(Vec::new());
+
+        let result = Server::read_body(&mut stream, &mut request, &mut storage);
+
+        assert!(matches!(result, Err(ParseError::InvalidHeader)));
+    }
+
+    #[test]
+    fn read_body_rejects_a_content_length_above_the_cap() {
+        let raw = format!(
+            "GET / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY_SIZE + 1
+        );
+        let mut request = Request::try_from(raw.as_bytes()).unwrap();
+        let mut storage = Vec::new();
+        let mut stream = Cursor::new(Vec::new());
+
+        let result = Server::read_body(&mut stream, &mut request, &mut storage);
+
+        assert!(matches!(result, Err(ParseError::PayloadTooLarge)));
+    }
+
+    #[test]
+    fn read_body_returns_pipelined_bytes_past_the_body_as_leftover() {
+        // The initial read grabbed this request's full body plus the start
+        // of the next pipelined request's line.
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhelloGET /next HTTP/1.1\r\n";
+        let mut request = Request::try_from(&raw[..]).unwrap();
+        let mut storage = Vec::new();
+        let mut stream = Cursor::new(Vec::new());
+
+        let leftover = Server::read_body(&mut stream, &mut request, &mut storage).unwrap();
+
+        assert_eq!(request.body(), b"hello");
+        assert_eq!(leftover, b"GET /next HTTP/1.1\r\n");
+    }
 }